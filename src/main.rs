@@ -36,20 +36,24 @@ async fn on_mouse_down() {
 fn main_loop() {
     UI_SPAWNER.with(|_| {}); // ensure initialized
     loop {
-        UI_POOL.with(|pool|{
-            let mut msg : MSG = unsafe { zeroed() };
+        let quit = UI_POOL.with(|pool|{
             let mut pool = pool.borrow_mut();
-            assert_ne!(unsafe { GetMessageW(&mut msg, null_mut(), 0, 0) }, -1);
-            unsafe { TranslateMessage(&msg) };
-            unsafe { DispatchMessageW(&msg) };
-            match msg.message {
-                WM_QUIT => return,
-                _       => {},
+
+            // Block until a message arrives, a registered HANDLE is signaled, or a timer elapses.
+            pool.wait_for_event();
+
+            // Drain every queued message before re-waiting.
+            let mut msg : MSG = unsafe { zeroed() };
+            while unsafe { PeekMessageW(&mut msg, null_mut(), 0, 0, PM_REMOVE) } != 0 {
+                if msg.message == WM_QUIT { return true; }
+                unsafe { TranslateMessage(&msg) };
+                unsafe { DispatchMessageW(&msg) };
             }
 
             pool.run_until_stalled();
+            false
         });
-        std::thread::yield_now(); // don't 100% our CPU maybe
+        if quit { break }
     }
 }
 
@@ -109,10 +113,5 @@ fn spawn_window() {
 }
 
 async fn wait_for(d: Duration) {
-    let (sender, receiver) = futures::channel::oneshot::channel::<()>();
-    std::thread::spawn(move ||{
-        std::thread::sleep(d);
-        let _ = sender.send(());
-    });
-    receiver.await.unwrap();
+    UI_SPAWNER.with(|s| s.sleep(d)).await; // coalesced onto the Pool's timer heap, no thread-per-sleep
 }
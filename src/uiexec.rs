@@ -1,88 +1,262 @@
-/// It'd be nice to debounce WM_NULL spam.  However, PostThreadMessageW is unreliable:
-///
-/// > Messages sent by PostThreadMessage are not associated with a window. As a general rule, messages that are not
-/// > associated with a window cannot be dispatched by the DispatchMessage function. Therefore, if the recipient thread
-/// > is in a modal loop (as used by MessageBox or DialogBox), the messages will be lost. To intercept thread messages
-/// > while in a modal loop, use a thread-specific hook.
-/// >
-/// > <https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-postthreadmessagew#remarks>
-///
-/// Currently the code bellow assumes WM_NULL is reliable for [`Spawner::spawn`]?
-/// That should instead set a tracking bool?
-/// Waker might also need to set a tracking bool *on top of* sending WM_NULL?
-const ERROR : () = compile_error!("PostThreadMessageW is unreliable!");
+//! A tiny single-threaded executor that polls [`Future`]s on the Win32 UI thread.
+//!
+//! Wakeups used to rely on `PostThreadMessageW(thread_id, WM_NULL, …)`, but thread messages are
+//! silently dropped whenever the thread enters a modal loop such as `MessageBoxW`/`DialogBoxW`:
+//!
+//! > Messages sent by PostThreadMessage are not associated with a window. [&hellip;] if the recipient thread
+//! > is in a modal loop (as used by MessageBox or DialogBox), the messages will be lost.
+//! >
+//! > <https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-postthreadmessagew#remarks>
+//!
+//! Window messages *are* dispatched inside modal loops, so the [`Pool`] instead owns a message-only
+//! window (`HWND_MESSAGE`) and wakes itself with `PostMessageW(hwnd, wake_message(), …)`.  The
+//! window's [`window_proc`] runs the executor when it receives that message, so futures keep making
+//! progress even while a `MessageBox` spun up by one of them is open.
 
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::winbase::{INFINITE, WAIT_FAILED, WAIT_OBJECT_0};
+use winapi::um::winnt::{HANDLE, MAXIMUM_WAIT_OBJECTS};
+use winapi::um::winuser::*;
 
+use wchar::wchz;
 
-use winapi::um::processthreadsapi::GetCurrentThreadId;
-use winapi::um::winuser::{PostThreadMessageW, WM_NULL};
-
-use std::collections::VecDeque;
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
 use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
-use std::sync::{Mutex, Arc, Weak};
+use std::ptr::null_mut;
+use std::sync::{Mutex, Arc, Weak, Once, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver};
 use std::task::{Waker, Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Process-unique window message used to nudge the executor awake.  Window (not thread) messages
+/// survive modal loops, which is the whole point of the message-only window.  Registered lazily via
+/// `RegisterWindowMessageW` so it can't collide with unrelated `WM_NULL`/`WM_APP` traffic.
+fn wake_message() -> UINT {
+    static CACHED : AtomicU32 = AtomicU32::new(0);
+    match CACHED.load(Ordering::Relaxed) {
+        0 => {
+            let id = unsafe { RegisterWindowMessageW(wchz!("uiexec::WM_WAKE").as_ptr()) };
+            assert!(id != 0);
+            CACHED.store(id, Ordering::Relaxed);
+            id
+        },
+        id => id,
+    }
+}
+
+/// `SetTimer` id for the backstop poll tick.  `WM_TIMER` is delivered inside modal loops too, so it
+/// guarantees the executor can't stall indefinitely if a wake message is ever lost.
+const WAKE_TIMER_ID : usize = 1;
+
+/// Default poll-tick interval used by [`Pool::new`].
+const DEFAULT_POLL_INTERVAL : Duration = Duration::from_millis(16);
+
+const WAKE_WND_CLASS : &[u16] = wchz!("uiexec::WakeWindow");
+
+/// Number of background workers backing [`Spawner::spawn_blocking`].
+const BLOCKING_WORKERS : usize = 4;
 
 
 
 pub struct Pool {
-    waker:              Waker,
-    executing:          VecDeque<Entry>,
-    shared:             Arc<Mutex<Shared>>,
+    hwnd:               HWND,
+    inner:              Box<Inner>,
     _not_thread_safe:   PhantomData<*const ()>, // not necessary for soundness... but using `Pool` directly from another thread is a bug
 }
 
 #[derive(Clone)]
 pub struct Spawner(Weak<Mutex<Shared>>);
 
+/// Heap-stable executor state.  Its address is stashed in the wake window's `GWLP_USERDATA` so
+/// [`window_proc`] can drive the executor; boxing keeps that pointer valid even as [`Pool`] moves.
+struct Inner {
+    hwnd:               HWND,
+    shared:             Arc<Mutex<Shared>>,
+    waker:              Waker,
+    poll_interval:      Duration,
+    timer_armed:        Cell<bool>,
+    /// At most one wake message may be outstanding; see [`post_wake`].
+    wake_posted:        Arc<AtomicBool>,
+}
+
 #[derive(Default)]
 struct Shared {
-    thread_id:          u32,
+    /// `HWND` of the message-only wake window as a `usize` so [`Shared`] stays `Send`.
+    hwnd:               usize,
+    next_id:            u64,
     pending:            VecDeque<Entry>,
+    executing:          VecDeque<Entry>,
+    /// Single min-heap of pending deadlines, keyed `(deadline, id)` so entries stay unique and
+    /// cancellable.  Replaces the old thread-per-sleep scheme in `wait_for`.
+    timers:             BTreeMap<(Instant, u64), Waker>,
+    next_timer_id:      u64,
+    /// Kernel objects awaited by [`Spawner::register_handle`], keyed by registration id.
+    handles:            BTreeMap<u64, HandleReg>,
+    next_handle_id:     u64,
+    /// Shared with the [`Waker`] and [`Inner`] so a chatty future posts at most one wake at a time.
+    wake_posted:        Arc<AtomicBool>,
+}
+
+struct HandleReg {
+    /// The `HANDLE` as a `usize` so [`Shared`] stays `Send`.
+    handle:             usize,
+    waker:              Option<Waker>,
+    signaled:           bool,
 }
 
 struct Entry {
-    future:             Pin<Box<dyn Future<Output = ()> + Send + 'static>>,
-    done:               bool,
+    id:                 u64,
+    /// `None` while this entry is mid-poll (possibly re-entrantly, behind a modal loop).
+    future:             Option<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>,
 }
 
 
 
 impl Pool {
-    pub fn new() -> Self {
-        let thread_id = unsafe { GetCurrentThreadId() };
+    pub fn new() -> Self { Self::with_poll_interval(DEFAULT_POLL_INTERVAL) }
 
-        let waker = waker_fn::waker_fn(move || assert!(unsafe { PostThreadMessageW(thread_id, WM_NULL, 0, 0) } != 0));
+    /// Like [`Pool::new`], but with a custom backstop poll-tick interval (see [`WAKE_TIMER_ID`]).
+    pub fn with_poll_interval(poll_interval: Duration) -> Self {
+        register_wake_class();
 
-        Self {
-            waker,
-            shared:             Arc::new(Mutex::new(Shared::new(thread_id))),
-            executing:          Default::default(),
-            _not_thread_safe:   Default::default(),
-        }
+        let hInstance = unsafe { GetModuleHandleW(null_mut()) };
+        assert!(!hInstance.is_null());
+
+        let hwnd = unsafe { CreateWindowExW(
+            0, WAKE_WND_CLASS.as_ptr(), null_mut(), 0,
+            0, 0, 0, 0,
+            HWND_MESSAGE, null_mut(), hInstance, null_mut(),
+        )};
+        assert!(!hwnd.is_null());
+
+        let wake_posted = Arc::new(AtomicBool::new(false));
+        let shared = Arc::new(Mutex::new(Shared { hwnd: hwnd as usize, wake_posted: wake_posted.clone(), .. Default::default() }));
+
+        let wake_hwnd = hwnd as usize;
+        let waker_flag = wake_posted.clone();
+        let waker = waker_fn::waker_fn(move || post_wake(wake_hwnd, &waker_flag));
+
+        let inner = Box::new(Inner { hwnd, shared, waker, poll_interval, timer_armed: Cell::new(false), wake_posted });
+        unsafe { SetWindowLongPtrW(hwnd, GWLP_USERDATA, &*inner as *const Inner as _) };
+
+        Self { hwnd, inner, _not_thread_safe: Default::default() }
     }
 
-    pub fn spawner(&self) -> Spawner { Spawner(Arc::downgrade(&self.shared)) }
+    pub fn spawner(&self) -> Spawner { Spawner(Arc::downgrade(&self.inner.shared)) }
 
-    pub fn run_until_stalled(&mut self) {
-        let mut shared = self.shared.lock().unwrap();
-        self.executing.append(&mut shared.pending);
-        drop(shared); // unlock
+    /// Polls every ready task until none make further progress.  Safe to call re-entrantly from
+    /// [`window_proc`] while a task is parked inside a modal loop: entries that are already mid-poll
+    /// are simply skipped.
+    pub fn run_until_stalled(&mut self) { self.inner.drive(); }
+
+    /// Blocks the UI thread until a window message arrives, a registered [`HANDLE`] is signaled, or
+    /// the nearest timer deadline elapses &mdash; then wakes the task awaiting the signaled handle, if
+    /// any (elapsed timers are fired by [`Pool::run_until_stalled`]).  This is the
+    /// backbone of the message loop: it lets UI-thread futures await kernel objects without busy
+    /// waiting, the way crosvm's window-procedure thread does.
+    pub fn wait_for_event(&self) {
+        // Collect handles still waiting to be signaled.  MsgWaitForMultipleObjectsEx reserves one
+        // slot of MAXIMUM_WAIT_OBJECTS for the input queue, so it accepts at most 63 handles; past
+        // that it returns WAIT_FAILED.  Any overflow gets picked up on a later pass once some of
+        // these signal.
+        let (ids, handles) : (Vec<u64>, Vec<HANDLE>) = {
+            let shared = self.inner.shared.lock().unwrap();
+            shared.handles.iter()
+                .filter(|(_, h)| !h.signaled)
+                .take(MAXIMUM_WAIT_OBJECTS as usize - 1)
+                .map(|(&id, h)| (id, h.handle as HANDLE))
+                .unzip()
+        };
 
+        let timeout = match self.inner.shared.lock().unwrap().next_deadline() {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()).as_millis().min(INFINITE as u128) as DWORD,
+            None           => INFINITE,
+        };
+
+        let signaled = unsafe { MsgWaitForMultipleObjectsEx(
+            handles.len() as DWORD, handles.as_ptr(), timeout,
+            QS_ALLINPUT, MWMO_INPUTAVAILABLE,
+        )};
+
+        if signaled == WAIT_FAILED {
+            // The wait itself failed (e.g. a stale handle slipped into the set).  Returning here
+            // would spin main_loop at 100% CPU, so block on the input queue alone until a message
+            // arrives; the bad handle's future will be re-examined on the next pass.
+            unsafe { MsgWaitForMultipleObjectsEx(
+                0, std::ptr::null(), timeout, QS_ALLINPUT, MWMO_INPUTAVAILABLE,
+            )};
+        } else if signaled >= WAIT_OBJECT_0 && signaled < WAIT_OBJECT_0 + handles.len() as DWORD {
+            let id = ids[(signaled - WAIT_OBJECT_0) as usize];
+            let mut shared = self.inner.shared.lock().unwrap();
+            if let Some(h) = shared.handles.get_mut(&id) {
+                h.signaled = true;
+                if let Some(waker) = h.waker.take() { waker.wake(); }
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn drive(&self) {
+        // Clear the debounce flag before draining so wakes raised mid-poll post a fresh message.
+        self.wake_posted.store(false, Ordering::Release);
         loop {
-            let mut any = false;
-            for e in self.executing.iter_mut() {
-                match e.future.as_mut().poll(&mut Context::from_waker(&self.waker)) {
-                    Poll::Pending => {},
+            // Snapshot the ids we'll poll this pass so freshly-spawned or re-entered tasks don't
+            // turn this into a busy-loop; they'll be picked up by the wake message they posted.
+            let ids : Vec<u64> = {
+                let mut shared = self.shared.lock().unwrap();
+                shared.fire_elapsed_timers();
+                let Shared { pending, executing, .. } = &mut *shared;
+                executing.append(pending);
+                executing.iter().filter(|e| e.future.is_some()).map(|e| e.id).collect()
+            };
+
+            let mut any_ready = false;
+            for id in ids {
+                // Take the future out (dropping the lock while we poll) so a re-entrant drive skips it.
+                let future = {
+                    let mut shared = self.shared.lock().unwrap();
+                    match shared.executing.iter_mut().find(|e| e.id == id) {
+                        Some(e) => e.future.take(),
+                        None    => None,
+                    }
+                };
+                let mut future = match future { Some(f) => f, None => continue };
+
+                match future.as_mut().poll(&mut Context::from_waker(&self.waker)) {
+                    Poll::Pending => {
+                        let mut shared = self.shared.lock().unwrap();
+                        if let Some(e) = shared.executing.iter_mut().find(|e| e.id == id) { e.future = Some(future); }
+                    },
                     Poll::Ready(()) => {
-                        e.done = true;
-                        any = true;
+                        let mut shared = self.shared.lock().unwrap();
+                        shared.executing.retain(|e| e.id != id);
+                        any_ready = true;
                     },
                 }
             }
-            if !any { return }
-            self.executing.retain(|e| !e.done);
+            if !any_ready { break }
+        }
+        self.sync_timer();
+    }
+
+    /// Arms the backstop poll timer only while tasks remain, so an idle app burns no CPU.
+    fn sync_timer(&self) {
+        let busy = { let shared = self.shared.lock().unwrap(); !shared.pending.is_empty() || !shared.executing.is_empty() || !shared.timers.is_empty() };
+        if busy && !self.timer_armed.get() {
+            let ms = self.poll_interval.as_millis().min(USER_TIMER_MAXIMUM as u128).max(USER_TIMER_MINIMUM as u128) as UINT;
+            assert!(unsafe { SetTimer(self.hwnd, WAKE_TIMER_ID, ms, None) } != 0);
+            self.timer_armed.set(true);
+        } else if !busy && self.timer_armed.get() {
+            unsafe { KillTimer(self.hwnd, WAKE_TIMER_ID) };
+            self.timer_armed.set(false);
         }
     }
 }
@@ -90,20 +264,300 @@ impl Pool {
 impl Spawner {
     /// Spawns work to execute on the UI thread the [`Pool`] was created on
     pub fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) -> Result<(), ()> {
-        let future = Box::pin(future);
         let shared = self.0.upgrade().ok_or(())?;
         let mut shared = shared.lock().map_err(|_| ())?;
-        let thread_id = shared.thread_id;
-        shared.pending.push_back(Entry { future, done: false });
+        let id = shared.next_id;
+        shared.next_id += 1;
+        shared.pending.push_back(Entry { id, future: Some(Box::pin(future)) });
+        let hwnd = shared.hwnd;
+        let wake_posted = shared.wake_posted.clone();
         drop(shared); // unlock
-        assert!(unsafe { PostThreadMessageW(thread_id, WM_NULL, 0, 0) } != 0);
+        post_wake(hwnd, &wake_posted);
         Ok(())
     }
 }
 
 impl Shared {
-    fn new(thread_id: u32) -> Self {
-        Self { thread_id, pending: Default::default() }
+    /// Wakes and removes every timer whose deadline has passed.
+    fn fire_elapsed_timers(&mut self) {
+        let now = Instant::now();
+        while let Some((&key, _)) = self.timers.iter().next() {
+            if key.0 > now { break }
+            let waker = self.timers.remove(&key).unwrap();
+            waker.wake();
+        }
+    }
+
+    /// The earliest pending deadline, if any &mdash; used to bound the UI thread's blocking wait.
+    fn next_deadline(&self) -> Option<Instant> { self.timers.keys().next().map(|k| k.0) }
+}
+
+impl Spawner {
+    /// Returns a future that resolves once `d` has elapsed, driven by the [`Pool`]'s timer heap
+    /// rather than a dedicated OS thread.
+    pub fn sleep(&self, d: Duration) -> Sleep { self.sleep_until(Instant::now() + d) }
+
+    /// Like [`Spawner::sleep`], but against an absolute deadline.
+    pub fn sleep_until(&self, deadline: Instant) -> Sleep {
+        let id = match self.0.upgrade() {
+            Some(shared) => { let mut shared = shared.lock().unwrap(); let id = shared.next_timer_id; shared.next_timer_id += 1; id },
+            None => 0,
+        };
+        Sleep { shared: self.0.clone(), deadline, id, registered: false }
+    }
+
+    /// Returns a future that resolves once `handle` becomes signaled.  The [`Pool`] feeds the handle
+    /// to [`Pool::wait_for_event`]'s `MsgWaitForMultipleObjectsEx` call; the caller retains ownership
+    /// of the handle.
+    pub fn register_handle(&self, handle: HANDLE) -> WaitHandle {
+        let id = match self.0.upgrade() {
+            Some(shared) => { let mut shared = shared.lock().unwrap(); let id = shared.next_handle_id; shared.next_handle_id += 1; id },
+            None => 0,
+        };
+        WaitHandle { shared: self.0.clone(), handle: handle as usize, id, registered: false }
+    }
+
+    /// Runs `future` with a deadline, resolving to [`Err`]`(`[`Elapsed`]`)` &mdash; and dropping the
+    /// inner future &mdash; if `d` elapses first.
+    pub fn timeout<F: Future>(&self, d: Duration, future: F) -> Timeout<F> {
+        Timeout { inner: Box::pin(future), sleep: self.sleep(d) }
+    }
+}
+
+/// Future returned by [`Spawner::sleep`].  Dropping it cancels its timer-heap entry.
+pub struct Sleep {
+    shared:     Weak<Mutex<Shared>>,
+    deadline:   Instant,
+    id:         u64,
+    registered: bool,
+}
+
+impl Future for Sleep {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = self.get_mut();
+        if Instant::now() >= this.deadline {
+            this.registered = false;
+            return Poll::Ready(());
+        }
+        if let Some(shared) = this.shared.upgrade() {
+            shared.lock().unwrap().timers.insert((this.deadline, this.id), cx.waker().clone());
+            this.registered = true;
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if !self.registered { return }
+        if let Some(shared) = self.shared.upgrade() { shared.lock().unwrap().timers.remove(&(self.deadline, self.id)); }
+    }
+}
+
+/// Future returned by [`Spawner::register_handle`].  Dropping it unregisters the handle.
+pub struct WaitHandle {
+    shared:     Weak<Mutex<Shared>>,
+    handle:     usize,
+    id:         u64,
+    registered: bool,
+}
+
+impl Future for WaitHandle {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = self.get_mut();
+        let shared = match this.shared.upgrade() { Some(s) => s, None => return Poll::Ready(()) };
+        let mut shared = shared.lock().unwrap();
+        let reg = shared.handles.entry(this.id).or_insert(HandleReg { handle: this.handle, waker: None, signaled: false });
+        if reg.signaled {
+            shared.handles.remove(&this.id);
+            this.registered = false;
+            return Poll::Ready(());
+        }
+        reg.waker = Some(cx.waker().clone());
+        this.registered = true;
+        Poll::Pending
+    }
+}
+
+impl Drop for WaitHandle {
+    fn drop(&mut self) {
+        if !self.registered { return }
+        if let Some(shared) = self.shared.upgrade() { shared.lock().unwrap().handles.remove(&self.id); }
+    }
+}
+
+/// The inner future did not complete before the [`Spawner::timeout`] deadline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elapsed;
+
+/// Future returned by [`Spawner::timeout`].
+pub struct Timeout<F> {
+    inner: Pin<Box<F>>,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Poll::Ready(output) = this.inner.as_mut().poll(cx) { return Poll::Ready(Ok(output)); }
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending   => Poll::Pending,
+        }
+    }
+}
+
+impl Spawner {
+    /// Like [`Spawner::spawn`], but returns a [`JoinHandle`] that resolves to the task's output.
+    ///
+    /// Dropping the handle detaches the task &mdash; it keeps running, its result is just discarded.
+    pub fn spawn_with_handle<T: Send + 'static>(&self, future: impl Future<Output = T> + Send + 'static) -> Result<JoinHandle<T>, ()> {
+        let state = Arc::new(Mutex::new(JoinState::<T> { result: None, panic: None, waker: None }));
+        let task_state = state.clone();
+        self.spawn(async move {
+            // Mirror `spawn_blocking`: a panicking future must not unwind through `drive()` (and so
+            // across the wake window's FFI boundary) nor leave the awaiter parked forever with
+            // `result`/`waker` unset.  Record the panic and re-raise it on `JoinHandle::poll`.
+            let mut future = Box::pin(future);
+            let outcome = std::future::poll_fn(move |cx| {
+                match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+                    Ok(poll)   => poll.map(Ok),
+                    Err(panic) => Poll::Ready(Err(panic)),
+                }
+            }).await;
+            let mut state = task_state.lock().unwrap();
+            match outcome {
+                Ok(output) => state.result = Some(output),
+                Err(panic) => state.panic  = Some(panic),
+            }
+            if let Some(waker) = state.waker.take() { waker.wake(); }
+        })?;
+        Ok(JoinHandle { state })
+    }
+
+    /// Offloads a blocking or CPU-heavy `f` to a shared background worker pool so it doesn't jank the
+    /// window, then resumes the awaiting task on the UI thread (the result wake posts to the [`Pool`]'s
+    /// message-only window via the stored [`Waker`]).
+    pub fn spawn_blocking<T: Send + 'static>(&self, f: impl FnOnce() -> T + Send + 'static) -> JoinHandle<T> {
+        let state = Arc::new(Mutex::new(JoinState::<T> { result: None, panic: None, waker: None }));
+        let job_state = state.clone();
+        let job : BlockingJob = Box::new(move || {
+            // Isolate the job from its worker: an unwinding `f` must neither take the worker thread
+            // down (it's shared by every future) nor leave the awaiter parked forever.  We record
+            // the panic and resume it on the UI thread when the `JoinHandle` is polled, mirroring
+            // how `window_proc` guards the FFI boundary in `main.rs`.
+            let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+            let mut state = job_state.lock().unwrap();
+            match outcome {
+                Ok(output) => state.result = Some(output),
+                Err(panic) => state.panic  = Some(panic),
+            }
+            if let Some(waker) = state.waker.take() { waker.wake(); }
+        });
+        let _ = blocking_pool().send(job);
+        JoinHandle { state }
+    }
+}
+
+type BlockingJob = Box<dyn FnOnce() + Send + 'static>;
+
+/// Lazily spins up [`BLOCKING_WORKERS`] threads draining a shared queue and returns the job sender.
+fn blocking_pool() -> &'static Sender<BlockingJob> {
+    static POOL : OnceLock<Sender<BlockingJob>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let (sender, receiver) = channel::<BlockingJob>();
+        let receiver : Arc<Mutex<Receiver<BlockingJob>>> = Arc::new(Mutex::new(receiver));
+        for _ in 0 .. BLOCKING_WORKERS {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || loop {
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_)  => break, // sender dropped: process exiting
+                }
+            });
+        }
+        sender
+    })
+}
+
+/// A handle to a task spawned with [`Spawner::spawn_with_handle`] or a job offloaded with
+/// [`Spawner::spawn_blocking`]; `.await` it to get its output.
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+struct JoinState<T> {
+    result: Option<T>,
+    /// A panic unwound out of a [`Spawner::spawn_blocking`] job or a [`Spawner::spawn_with_handle`]
+    /// task; resumed on the awaiter so the
+    /// failure surfaces instead of the handle hanging forever.
+    panic:  Option<Box<dyn Any + Send + 'static>>,
+    waker:  Option<Waker>,
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(panic) = state.panic.take() { std::panic::resume_unwind(panic); }
+        match state.result.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+/// Posts the wake message to the message-only window so the executor runs, even inside a modal loop.
+///
+/// Bounded to a single outstanding wake: only the caller that flips `wake_posted` `false`&rarr;`true`
+/// actually posts, so a chatty future can't flood the message queue.  [`Inner::drive`] clears the
+/// flag before draining.
+fn post_wake(hwnd: usize, wake_posted: &AtomicBool) {
+    if wake_posted.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+        assert!(unsafe { PostMessageW(hwnd as HWND, wake_message(), 0, 0) } != 0);
+    }
+}
+
+fn register_wake_class() {
+    static ONCE : Once = Once::new();
+    ONCE.call_once(|| {
+        let hInstance = unsafe { GetModuleHandleW(null_mut()) };
+        assert!(!hInstance.is_null());
+        let wc = WNDCLASSW { lpfnWndProc: Some(window_proc), hInstance, lpszClassName: WAKE_WND_CLASS.as_ptr(), .. unsafe { std::mem::zeroed() } };
+        assert!(unsafe { RegisterClassW(&wc) } != 0);
+    });
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, uMsg: UINT, wParam: WPARAM, lParam: LPARAM) -> LRESULT {
+    if uMsg == wake_message() || uMsg == WM_TIMER {
+        // Futures are polled here, so a panicking task would unwind across this `extern "system"`
+        // callback boundary (undefined behavior); guard it the same way `main.rs`'s `window_proc`
+        // does.  Covers both the wake message and the `WM_TIMER` backstop tick.
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let inner = unsafe { GetWindowLongPtrW(hwnd, GWLP_USERDATA) } as *const Inner;
+            if let Some(inner) = unsafe { inner.as_ref() } { inner.drive(); }
+        })).unwrap_or_else(|panic| {
+            eprintln!("uiexec::window_proc paniced: {:?}", panic);
+            std::process::abort();
+        });
+        0
+    } else {
+        unsafe { DefWindowProcW(hwnd, uMsg, wParam, lParam) }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        if self.inner.timer_armed.get() { unsafe { KillTimer(self.hwnd, WAKE_TIMER_ID) }; }
+        unsafe { SetWindowLongPtrW(self.hwnd, GWLP_USERDATA, 0) };
+        unsafe { DestroyWindow(self.hwnd) };
     }
 }
 